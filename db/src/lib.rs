@@ -1,22 +1,39 @@
 use migration::run_migration;
-use sqlx::sqlite;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 use std::env;
 
 mod migration;
 
-pub type ConnectionPool = sqlite::SqlitePool;
+pub type ConnectionPool = DatabaseConnection;
 
-pub async fn new_connection_pool() -> ConnectionPool {
+fn database_url() -> String {
     let db_addr_key = "DATABASE_URL";
-    let db_addr = match env::var("UNITTEST") {
+    match env::var("UNITTEST") {
         Ok(_) => String::from("sqlite://:memory:"),
         _other => env::var(&db_addr_key).expect("DATABASE_URL not set"),
+    }
+}
+
+/// Connects to whichever backend `DATABASE_URL` points at (`sqlite:`, `postgres:` or
+/// `mysql:`), sizing the pool off the number of CPUs rather than a fixed connection
+/// count, then runs migrations against that same connection so the schema they
+/// create is visible to every query made through it.
+///
+/// The one exception is `sqlite://:memory:`: every connection opened against it is
+/// its own distinct, empty database, so a pool of more than one connection would
+/// have migrations land on whichever connection ran them while queries round-robin
+/// across others that never saw the schema. That path is clamped to a single
+/// connection instead.
+pub async fn new_connection_pool() -> ConnectionPool {
+    let db_addr = database_url();
+    let max_connections = if db_addr.contains(":memory:") {
+        1
+    } else {
+        num_cpus::get() as u32
     };
-    let pool = sqlite::SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&db_addr)
-        .await
-        .unwrap();
-    run_migration(&pool).await;
-    pool
+    let mut options = ConnectOptions::new(db_addr);
+    options.max_connections(max_connections);
+    let conn = Database::connect(options).await.unwrap();
+    run_migration(&conn).await;
+    conn
 }
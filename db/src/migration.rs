@@ -1,6 +1,26 @@
-use sqlx::sqlite;
-use sqlx::{self, migrate};
+use sea_orm::{DatabaseConnection, DbBackend};
+use sqlx::migrate;
 
-pub async fn run_migration(db: &sqlite::SqlitePool) {
-    migrate!().run(db).await.unwrap();
+/// Runs the migration set matching `conn`'s backend against the exact
+/// connection pool `conn` itself queries through, rather than a second pool
+/// opened separately — for `sqlite://:memory:` in particular, a second
+/// connection would see an entirely different in-memory database and the
+/// schema created here would be invisible to `conn`. Each backend ships its
+/// own migration directory since the DDL (auto-increment columns, timestamp
+/// types) isn't portable across sqlite/postgres/mysql.
+pub async fn run_migration(conn: &DatabaseConnection) {
+    match conn.get_database_backend() {
+        DbBackend::Sqlite => migrate!("./migrations/sqlite")
+            .run(conn.get_sqlite_connection_pool())
+            .await
+            .unwrap(),
+        DbBackend::Postgres => migrate!("./migrations/postgres")
+            .run(conn.get_postgres_connection_pool())
+            .await
+            .unwrap(),
+        DbBackend::MySql => migrate!("./migrations/mysql")
+            .run(conn.get_mysql_connection_pool())
+            .await
+            .unwrap(),
+    }
 }
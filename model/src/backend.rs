@@ -0,0 +1,27 @@
+use sea_orm::entity::prelude::*;
+
+/// A single remote address backing a [`crate::service::Model`]. A service with
+/// more than one backend is load balanced across them by the proxy layer.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "backend")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub service_id: i32,
+    pub address: String,
+    pub created_at: ChronoDateTime,
+    pub updated_at: Option<ChronoDateTime>,
+    pub deleted_at: Option<ChronoDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::service::Entity",
+        from = "Column::ServiceId",
+        to = "super::service::Column::Id"
+    )]
+    Service,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
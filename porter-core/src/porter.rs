@@ -6,7 +6,10 @@ use tokio::{self, runtime};
 
 use crate::{
     event::Event,
-    types::{CreateServiceInput, Error, IntoActiveModel, UpdateServiceInput},
+    types::{
+        string_to_strategy, string_to_transport, CreateServiceInput, Error, IntoActiveModel,
+        UpdateServiceInput,
+    },
     CreateGateInput, UpdateGateInput,
 };
 
@@ -59,10 +62,18 @@ impl Porter {
     }
 
     /// Create a service.
+    ///
+    /// When `transport` is [`proxy::TransportType::Tls`], `cert_pem`/`key_pem` are parsed
+    /// eagerly so a malformed certificate or key is rejected here instead of at first connection.
     pub async fn create_service(
         &self,
         input: CreateServiceInput,
     ) -> Result<model::service::Model, Error> {
+        if input.transport == proxy::TransportType::Tls {
+            let cert_pem = input.cert_pem.as_ref().ok_or("cert_pem is required for tls transport")?;
+            let key_pem = input.key_pem.as_ref().ok_or("key_pem is required for tls transport")?;
+            proxy::tls::build_tls_acceptor(cert_pem.as_bytes(), key_pem.as_bytes())?;
+        }
         Ok(input.into_active_model().insert(self.get_db()?).await?)
     }
 
@@ -115,22 +126,36 @@ impl Porter {
         Ok(gate)
     }
 
+    /// Fetches the live traffic metrics for a gate's proxy.
+    pub async fn get_gate_metrics(&self, id: i32) -> Result<proxy::Metrics, Error> {
+        let gate = model::gate::Entity::find_by_id(id)
+            .one(self.get_db()?)
+            .await?
+            .ok_or(format!("gate {} not found", id))?;
+        Ok(self.proxy_manager.proxy_metrics(gate.port).await?)
+    }
+
     pub async fn create_gate(
         &mut self,
         input: CreateGateInput,
     ) -> Result<model::gate::Model, Error> {
         let gate = input.into_active_model().insert(self.get_db()?).await?;
-        let service = gate
-            .find_related(model::service::Entity)
-            .one(self.get_db()?)
-            .await?
-            .ok_or(format!("service for gate {} not found", gate.service_id))?;
-        self.proxy_manager.create_proxy(
-            gate.id,
-            &gate.host,
-            gate.port,
-            &format!("{}:{}", &service.host, service.port),
-        );
+        let (backends, tls, upstream_proxy) = self
+            .resolve_gate_wiring(gate.service_id, string_to_transport(&gate.transport))
+            .await?;
+        self.proxy_manager
+            .create_proxy(
+                gate.host.clone(),
+                gate.port,
+                backends,
+                string_to_strategy(&gate.strategy),
+                tls,
+                proxy::PoolConfig::default(),
+                proxy::DEFAULT_DRAIN_TIMEOUT,
+                gate.pin_static,
+                upstream_proxy,
+            )
+            .await?;
         Ok(gate)
     }
 
@@ -139,21 +164,85 @@ impl Porter {
         id: i32,
         update: UpdateGateInput,
     ) -> Result<model::gate::Model, Error> {
+        // Captured before the update lands so that an update which changes the gate's
+        // port still drains the proxy actually listening on the *old* port, not a
+        // no-op delete of the new one.
+        let old_port = model::gate::Entity::find_by_id(id)
+            .one(self.get_db()?)
+            .await?
+            .ok_or(format!("gate {} not found", id))?
+            .port;
         let mut update = update.into_active_model();
         update.id = Unchanged(id);
         update.updated_at = Set(Some(chrono::Utc::now().naive_utc()));
+        // `update` only `Set`s the fields the caller actually supplied (the rest stay
+        // `NotSet`), so the returned row reflects unspecified fields as they already
+        // were in the database rather than some hardcoded default.
         let gate = update.update(self.get_db()?).await?;
-        let service = gate
-            .find_related(model::service::Entity)
+        let (backends, tls, upstream_proxy) = self
+            .resolve_gate_wiring(gate.service_id, string_to_transport(&gate.transport))
+            .await?;
+        // Drain the old proxy before standing up the new one so the port is
+        // free and no in-flight connections are abruptly cut off mid-update.
+        self.proxy_manager.delete_proxy(old_port).await?;
+        self.proxy_manager
+            .create_proxy(
+                gate.host.clone(),
+                gate.port,
+                backends,
+                string_to_strategy(&gate.strategy),
+                tls,
+                proxy::PoolConfig::default(),
+                proxy::DEFAULT_DRAIN_TIMEOUT,
+                gate.pin_static,
+                upstream_proxy,
+            )
+            .await?;
+        Ok(gate)
+    }
+
+    /// Resolves the live backend addresses for the service behind a gate, along with the
+    /// TLS acceptor/connector to terminate/originate the proxy's two legs and the upstream
+    /// HTTP CONNECT proxy (if any) to tunnel outbound connections through.
+    async fn resolve_gate_wiring(
+        &self,
+        service_id: i32,
+        gate_transport: proxy::TransportType,
+    ) -> Result<(Vec<String>, proxy::ProxyTls, Option<String>), Error> {
+        let service = model::service::Entity::find_by_id(service_id)
             .one(self.get_db()?)
             .await?
-            .ok_or(format!("service for gate {} not found", id))?;
-        self.proxy_manager.create_proxy(
-            gate.host.clone(),
-            gate.port,
-            format!("{}:{}", &service.host, service.port),
-        );
-        Ok(gate)
+            .ok_or(format!("service {} not found", service_id))?;
+        let backends = service
+            .find_related(model::backend::Entity)
+            .all(self.get_db()?)
+            .await?
+            .into_iter()
+            .map(|backend| backend.address)
+            .collect();
+        let inbound_acceptor = match gate_transport {
+            proxy::TransportType::Tls => {
+                let cert_pem = service.cert_pem.as_ref().ok_or("service has no cert_pem")?;
+                let key_pem = service.key_pem.as_ref().ok_or("service has no key_pem")?;
+                Some(std::sync::Arc::new(proxy::tls::build_tls_acceptor(
+                    cert_pem.as_bytes(),
+                    key_pem.as_bytes(),
+                )?))
+            }
+            proxy::TransportType::Tcp => None,
+        };
+        let outbound_connector = match service.transport.as_str() {
+            "tls" => Some(std::sync::Arc::new(proxy::tls::build_tls_connector()?)),
+            _ => None,
+        };
+        Ok((
+            backends,
+            proxy::ProxyTls {
+                inbound_acceptor,
+                outbound_connector,
+            },
+            service.upstream_proxy,
+        ))
     }
 
     pub async fn delete_gate(&mut self, id: i32) -> Result<(), Error> {
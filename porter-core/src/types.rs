@@ -1,4 +1,5 @@
 use model::{self, ActiveValue, NotSet, Set, Value};
+use proxy;
 use std::error::Error as StdError;
 
 pub type Error = Box<dyn StdError + Send + Sync>;
@@ -14,23 +15,75 @@ pub trait IntoActiveModel<T> {
 pub struct CreateServiceInput {
     pub host: String,
     pub port: i32,
+    /// PEM-encoded certificate chain and private key that gates fronting this service use
+    /// to terminate TLS when the gate's own transport is `Tls`. Required (and validated)
+    /// at creation when this service's `transport` is `Tls`, which itself only makes the
+    /// outbound leg to the backends TLS.
+    pub cert_pem: Option<String>,
+    pub key_pem: Option<String>,
+    pub transport: proxy::TransportType,
+    /// Upstream HTTP CONNECT proxy to tunnel outbound connections through, as
+    /// `[user:pass@]host:port`. Required when the only egress path is a corporate proxy.
+    pub upstream_proxy: Option<String>,
 }
 
 pub struct UpdateServiceInput {
     pub host: Option<String>,
     pub port: Option<i32>,
+    pub cert_pem: Option<String>,
+    pub key_pem: Option<String>,
+    pub transport: Option<proxy::TransportType>,
+    pub upstream_proxy: Option<String>,
 }
 
 pub struct CreateGateInput {
     pub service_id: i32,
     pub host: String,
     pub port: i32,
+    pub strategy: proxy::LoadBalanceStrategy,
+    pub transport: proxy::TransportType,
+    /// When true, backend addresses are dialed as-is with DNS resolution bypassed
+    /// instead of being periodically re-resolved.
+    pub pin_static: bool,
 }
 
 pub struct UpdateGateInput {
     pub service_id: Option<i32>,
     pub host: Option<String>,
     pub port: Option<i32>,
+    pub strategy: Option<proxy::LoadBalanceStrategy>,
+    pub transport: Option<proxy::TransportType>,
+    pub pin_static: Option<bool>,
+}
+
+fn transport_to_string(transport: proxy::TransportType) -> String {
+    match transport {
+        proxy::TransportType::Tcp => "tcp".to_owned(),
+        proxy::TransportType::Tls => "tls".to_owned(),
+    }
+}
+
+pub(crate) fn string_to_transport(transport: &str) -> proxy::TransportType {
+    match transport {
+        "tls" => proxy::TransportType::Tls,
+        _ => proxy::TransportType::Tcp,
+    }
+}
+
+fn strategy_to_string(strategy: proxy::LoadBalanceStrategy) -> String {
+    match strategy {
+        proxy::LoadBalanceStrategy::RoundRobin => "round_robin".to_owned(),
+        proxy::LoadBalanceStrategy::LeastConnections => "least_connections".to_owned(),
+        proxy::LoadBalanceStrategy::Random => "random".to_owned(),
+    }
+}
+
+pub(crate) fn string_to_strategy(strategy: &str) -> proxy::LoadBalanceStrategy {
+    match strategy {
+        "least_connections" => proxy::LoadBalanceStrategy::LeastConnections,
+        "random" => proxy::LoadBalanceStrategy::Random,
+        _ => proxy::LoadBalanceStrategy::RoundRobin,
+    }
 }
 
 impl IntoActiveModel<model::service::ActiveModel> for CreateServiceInput {
@@ -38,6 +91,10 @@ impl IntoActiveModel<model::service::ActiveModel> for CreateServiceInput {
         model::service::ActiveModel {
             host: self.host.into_active_value(),
             port: self.port.into_active_value(),
+            cert_pem: self.cert_pem.into_active_value(),
+            key_pem: self.key_pem.into_active_value(),
+            transport: transport_to_string(self.transport).into_active_value(),
+            upstream_proxy: self.upstream_proxy.into_active_value(),
             ..Default::default()
         }
     }
@@ -48,6 +105,10 @@ impl IntoActiveModel<model::service::ActiveModel> for UpdateServiceInput {
         model::service::ActiveModel {
             host: self.host.into_active_value(),
             port: self.port.into_active_value(),
+            cert_pem: self.cert_pem.into_active_value(),
+            key_pem: self.key_pem.into_active_value(),
+            transport: self.transport.map(transport_to_string).into_active_value(),
+            upstream_proxy: self.upstream_proxy.into_active_value(),
             ..Default::default()
         }
     }
@@ -59,6 +120,9 @@ impl IntoActiveModel<model::gate::ActiveModel> for CreateGateInput {
             service_id: self.service_id.into_active_value(),
             host: self.host.into_active_value(),
             port: self.port.into_active_value(),
+            strategy: strategy_to_string(self.strategy).into_active_value(),
+            transport: transport_to_string(self.transport).into_active_value(),
+            pin_static: self.pin_static.into_active_value(),
             ..Default::default()
         }
     }
@@ -70,6 +134,9 @@ impl IntoActiveModel<model::gate::ActiveModel> for UpdateGateInput {
             service_id: self.service_id.into_active_value(),
             host: self.host.into_active_value(),
             port: self.port.into_active_value(),
+            strategy: self.strategy.map(strategy_to_string).into_active_value(),
+            transport: self.transport.map(transport_to_string).into_active_value(),
+            pin_static: self.pin_static.into_active_value(),
             ..Default::default()
         }
     }
@@ -0,0 +1,24 @@
+use async_graphql::SimpleObject;
+use proxy::Metrics;
+
+#[derive(SimpleObject)]
+#[graphql(name = "GateMetrics")]
+pub struct GateMetricsDTO {
+    pub ready: bool,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub total_connections: u64,
+    pub active_connections: u64,
+}
+
+impl GateMetricsDTO {
+    pub fn from_metrics(metrics: Metrics, ready: bool) -> Self {
+        Self {
+            ready,
+            bytes_in: metrics.bytes_in,
+            bytes_out: metrics.bytes_out,
+            total_connections: metrics.total_connections,
+            active_connections: metrics.active_connections,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::{sync::Arc, time::Duration};
+
+use async_graphql::{Context, Result, Subscription};
+use futures_util::{Stream, StreamExt};
+use porter_core::Porter;
+use tokio_stream::wrappers::IntervalStream;
+
+use self::dto::GateMetricsDTO;
+
+mod dto;
+
+#[derive(Default)]
+pub struct GateSubscription;
+
+#[Subscription]
+impl GateSubscription {
+    /// Streams a gate's live traffic metrics and ready-state once per second.
+    async fn gate_metrics(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> Result<impl Stream<Item = GateMetricsDTO>> {
+        let porter = ctx.data::<Arc<Porter>>()?.clone();
+        Ok(
+            IntervalStream::new(tokio::time::interval(Duration::from_secs(1))).filter_map(
+                move |_| {
+                    let porter = porter.clone();
+                    async move {
+                        let ready = porter
+                            .get_gate(id)
+                            .await
+                            .map(|gate| gate.ready)
+                            .unwrap_or(false);
+                        let metrics = porter.get_gate_metrics(id).await.ok()?;
+                        Some(GateMetricsDTO::from_metrics(metrics, ready))
+                    }
+                },
+            ),
+        )
+    }
+}
@@ -1,15 +1,33 @@
+use std::sync::Arc;
+
 use porter_core::Porter;
 use tokio;
-use warp;
+use warp::{self, Filter};
 
 mod dto;
+mod gate;
+mod schema;
 mod service;
 
+use crate::schema::ApiSchema;
+
 #[tokio::main]
 async fn main() {
     let porter = Porter::new();
     porter = porter.init().await;
-    let app = warp::path!("graphql");
+    let porter = Arc::new(porter);
+    let schema = schema::build_schema(porter.clone());
+    let app = warp::path!("graphql")
+        .and(async_graphql_warp::graphql_subscription(schema.clone()))
+        .or(warp::path!("graphql")
+            .and(async_graphql_warp::graphql(schema))
+            .and_then(
+                |(schema, request): (ApiSchema, async_graphql::Request)| async move {
+                    Ok::<_, std::convert::Infallible>(async_graphql_warp::GraphQLResponse::from(
+                        schema.execute(request).await,
+                    ))
+                },
+            ));
     tokio::join!(async {
         warp::serve(app).run(([127, 0, 0, 1], 80)).await;
     },async {
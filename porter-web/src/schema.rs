@@ -0,0 +1,17 @@
+use std::sync::Arc;
+
+use async_graphql::{EmptyMutation, Schema};
+use porter_core::Porter;
+
+use crate::{gate::GateSubscription, service::ServiceQuery};
+
+/// Porter's GraphQL API: queries from [`ServiceQuery`] and the live gate
+/// metrics subscription from [`GateSubscription`], both resolving their
+/// [`Porter`] handle from the same `Arc<Porter>` context value.
+pub type ApiSchema = Schema<ServiceQuery, EmptyMutation, GateSubscription>;
+
+pub fn build_schema(porter: Arc<Porter>) -> ApiSchema {
+    Schema::build(ServiceQuery, EmptyMutation, GateSubscription)
+        .data(porter)
+        .finish()
+}
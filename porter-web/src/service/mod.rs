@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use async_graphql::{Context, Object, Result};
 use porter_core::Porter;
 
@@ -16,7 +18,7 @@ impl ServiceQuery {
         ctx: &Context<'_>,
         input: ServiceInputDTO,
     ) -> Result<ServiceDTO> {
-        let porter = ctx.data::<Porter>()?;
+        let porter = ctx.data::<Arc<Porter>>()?;
         Ok(ServiceDTO::from(porter.create_service(input.into_active_model()).await?))
     }
 }
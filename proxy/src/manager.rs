@@ -1,4 +1,4 @@
-use std::{collections::HashMap, time};
+use std::{collections::HashMap, sync::Arc, time};
 use tokio::{
     runtime,
     sync::{mpsc, oneshot, watch},
@@ -6,10 +6,12 @@ use tokio::{
 };
 
 use super::proxy::Proxy;
-use crate::types::{Error, Request, Response};
+use crate::pool::PoolConfig;
+use crate::resolver::CachingResolver;
+use crate::types::{Error, LoadBalanceStrategy, Metrics, ProxyTls, Request, Response};
 
-/// A TCP proxy connecting local ports to remote addresses.
-/// Currently only 1-to-1 relation is supported, i.e. no loadbalancing.
+/// A TCP proxy connecting local ports to one or more remote backends,
+/// load balanced according to a [`LoadBalanceStrategy`].
 pub struct ProxyManager {
     sender: mpsc::Sender<Request>,
     daemon: JoinHandle<()>,
@@ -33,6 +35,9 @@ impl ProxyManager {
         let (sender, mut receiver) = mpsc::channel::<Request>(8);
         let req_sender = sender.clone();
         let (ready_sender, ready_receiver) = watch::channel(false);
+        let resolver = Arc::new(
+            CachingResolver::new().expect("failed to initialize the proxy manager's dns resolver"),
+        );
         Self {
             sender,
             ready_watcher: ready_receiver,
@@ -53,7 +58,13 @@ impl ProxyManager {
                         Request::CreateProxy {
                             host,
                             port,
-                            remote_addr,
+                            backends,
+                            strategy,
+                            tls,
+                            pool_config,
+                            drain_timeout,
+                            pin_static,
+                            upstream_proxy,
                             response_channel,
                         } => {
                             if terminating {
@@ -71,7 +82,19 @@ impl ProxyManager {
                                 let request_sender = req_sender.clone();
                                 proxy_map.insert(
                                     port,
-                                    Proxy::new(&host, port, &remote_addr, close_sender),
+                                    Proxy::new(
+                                        &host,
+                                        port,
+                                        backends,
+                                        strategy,
+                                        tls,
+                                        pool_config,
+                                        drain_timeout,
+                                        pin_static,
+                                        upstream_proxy,
+                                        resolver.clone(),
+                                        close_sender,
+                                    ),
                                 );
                                 watcher_map.insert(
                                     port,
@@ -87,7 +110,17 @@ impl ProxyManager {
                                 );
                             }
                         }
+                        Request::ProxyMetrics(port, res) => {
+                            if let Some(proxy) = proxy_map.get(&port) {
+                                res.send(Ok(proxy.metrics())).ok();
+                            } else {
+                                res.send(Err(ProxyManager::PROXY_NOT_READY.into())).ok();
+                            }
+                        }
                         Request::DeleteProxy(port, response_maybe) => {
+                            if let Some(proxy) = proxy_map.get(&port) {
+                                proxy.drain().await;
+                            }
                             proxy_map.remove(&port);
                             if let Some(handle) = watcher_map.remove(&port) {
                                 handle.abort();
@@ -154,25 +187,48 @@ impl ProxyManager {
         }
     }
 
-    /// Create a proxy listening on a port and connecting to a remote address.
+    /// Create a proxy listening on a port and fanning out to a set of backends.
     ///
     /// - host: the host from which the proxy should accept the requests. e.g. "127.0.0.1" for only
     /// accepting requests from the localhost.
     /// - port: the local port on which the proxy should listen.
-    /// - remote_addr: the complete address of the remote service. e.g. google.com:80
+    /// - backends: the complete addresses of the remote backends. e.g. google.com:80
+    /// - strategy: how to pick a backend among `backends` for each inbound connection.
+    /// - tls: TLS material to terminate the inbound leg and/or originate the outbound leg.
+    /// - pool_config: size and idle TTL of the pre-warmed outbound connection pool kept per backend.
+    /// - drain_timeout: how long this proxy waits for in-flight connections to finish on their
+    /// own once it is asked to stop, before they are left to be aborted outright.
+    /// - pin_static: when true, `backends` are dialed as literal addresses with DNS resolution
+    /// bypassed; when false, each backend is resolved (and periodically re-resolved) so a
+    /// single DNS name with multiple records behaves as multiple load-balanced backends.
+    /// - upstream_proxy: an optional upstream HTTP CONNECT proxy (`[user:pass@]host:port`) to
+    /// tunnel every outbound backend connection through, for networks where it's the only
+    /// egress path.
     ///
     /// Returns Ok if the proxy is successfully created.
     pub async fn create_proxy(
         &self,
         host: String,
         port: i32,
-        remote_addr: String,
+        backends: Vec<String>,
+        strategy: LoadBalanceStrategy,
+        tls: ProxyTls,
+        pool_config: PoolConfig,
+        drain_timeout: time::Duration,
+        pin_static: bool,
+        upstream_proxy: Option<String>,
     ) -> Result<(), Error> {
         let (res_sender, res_receiver) = oneshot::channel();
         self.send_request(Request::CreateProxy {
             host,
             port,
-            remote_addr,
+            backends,
+            strategy,
+            tls,
+            pool_config,
+            drain_timeout,
+            pin_static,
+            upstream_proxy,
             response_channel: res_sender,
         })
         .await;
@@ -185,8 +241,21 @@ impl ProxyManager {
         }
     }
 
+    /// Fetch the live traffic metrics for a proxy.
+    ///
+    /// Returns an error if the proxy is not currently running.
+    pub async fn proxy_metrics(&self, port: i32) -> Result<Metrics, Error> {
+        let (res_sender, res_receiver) = oneshot::channel();
+        self.send_request(Request::ProxyMetrics(port, res_sender))
+            .await;
+        tokio::time::timeout(ProxyManager::REQUEST_TIMEOUT, res_receiver).await??
+    }
+
     /// Delete a proxy.
     ///
+    /// Stops it from accepting new inbound connections and waits up to its configured
+    /// drain timeout for in-flight ones to finish before tearing it down.
+    ///
     /// Returns Ok if the proxy is not found or successfully deleted.
     pub async fn delete_proxy(&self, port: i32) -> Result<(), Error> {
         let (res_sender, res_receiver) = oneshot::channel();
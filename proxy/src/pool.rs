@@ -0,0 +1,94 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpStream, sync::Mutex, time::timeout};
+
+/// Bounds on the pool of pre-warmed outbound connections kept per backend.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    pub max_size: usize,
+    pub idle_ttl: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 64,
+            idle_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+struct Idle {
+    stream: TcpStream,
+    since: Instant,
+}
+
+/// A bounded, per-backend pool of already-connected `TcpStream`s, so a fresh
+/// inbound connection can skip the TCP handshake when a warm one is available.
+pub struct ConnectionPool {
+    config: PoolConfig,
+    idle: Mutex<HashMap<String, VecDeque<Idle>>>,
+}
+
+impl ConnectionPool {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a pooled connection for `addr`, if one is available and still alive.
+    pub async fn take(&self, addr: &str) -> Option<TcpStream> {
+        let mut idle = self.idle.lock().await;
+        let conns = idle.get_mut(addr)?;
+        while let Some(candidate) = conns.pop_back() {
+            if Self::is_alive(&candidate.stream).await {
+                return Some(candidate.stream);
+            }
+        }
+        None
+    }
+
+    /// Returns a connection to the pool for reuse, unless it is full or the
+    /// connection already looks dead.
+    pub async fn put(&self, addr: &str, stream: TcpStream) {
+        if self.config.max_size == 0 || !Self::is_alive(&stream).await {
+            return;
+        }
+        let mut idle = self.idle.lock().await;
+        let conns = idle.entry(addr.to_owned()).or_default();
+        if conns.len() < self.config.max_size {
+            conns.push_back(Idle {
+                stream,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// Drops every pooled connection older than `idle_ttl`. Meant to be driven
+    /// by a periodic background task.
+    pub async fn evict_expired(&self) {
+        let mut idle = self.idle.lock().await;
+        for conns in idle.values_mut() {
+            conns.retain(|c| c.since.elapsed() < self.config.idle_ttl);
+        }
+    }
+
+    pub fn idle_ttl(&self) -> Duration {
+        self.config.idle_ttl
+    }
+
+    /// A cheap, non-destructive liveness probe: polls read-readiness without
+    /// reading any bytes, so (unlike a `try_read`) it never steals data the
+    /// next user of a reused connection would otherwise see. An idle,
+    /// still-open connection has nothing to read and so isn't readable; one
+    /// that became readable has either had its peer hang up (EOF) or sent
+    /// unsolicited bytes, and either way isn't safe to hand out as a fresh
+    /// connection.
+    async fn is_alive(stream: &TcpStream) -> bool {
+        !matches!(timeout(Duration::ZERO, stream.readable()).await, Ok(Ok(())))
+    }
+}
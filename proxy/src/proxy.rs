@@ -1,46 +1,531 @@
-use std::error::Error;
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use tokio::{
-    io::{self, AsyncWriteExt},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{TcpListener, TcpStream},
-    sync::oneshot,
+    sync::{oneshot, watch},
     task::JoinHandle,
 };
+use tokio_rustls::{client::TlsStream, rustls::ServerName};
+
+use crate::pool::{ConnectionPool, PoolConfig};
+use crate::resolver::{BackendSpec, CachingResolver, ResolvedBackend, MIN_REFRESH_INTERVAL};
+use crate::types::{LoadBalanceStrategy, Metrics, ProxyTls};
+
+/// How often [`Proxy::drain`] polls the active-connection counter while waiting
+/// for in-flight connections to finish on their own.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub type ProxyResult = Result<(), Box<dyn Error + Send + Sync>>;
 
+/// A plaintext or TLS-wrapped stream, so the relay loop doesn't care which.
+pub trait IoStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> IoStream for T {}
+
+/// How long a backend is skipped for after a failed connection attempt.
+const BACKEND_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// Per-backend bookkeeping used by the load balancer: how many connections are
+/// currently proxied through it, and whether it was recently marked down.
+struct Backend {
+    addr: String,
+    /// Hostname to use for TLS SNI/certificate validation. For a DNS-resolved
+    /// backend this is the original name, not the resolved IP `addr` dials.
+    sni_host: String,
+    in_flight: Arc<AtomicUsize>,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn new(resolved: ResolvedBackend) -> Self {
+        Self {
+            addr: resolved.addr,
+            sni_host: resolved.sni_host,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            down_until: Mutex::new(None),
+        }
+    }
+
+    /// Rebuilds a backend from `resolved`, sharing `existing`'s in-flight
+    /// counter and copying its cooldown so a DNS re-resolve that happens to return
+    /// the same address doesn't reset load-balancing/health state for it.
+    fn carry_over(resolved: ResolvedBackend, existing: &Backend) -> Self {
+        Self {
+            addr: resolved.addr,
+            sni_host: resolved.sni_host,
+            in_flight: existing.in_flight.clone(),
+            down_until: Mutex::new(*existing.down_until.lock().unwrap()),
+        }
+    }
+
+    fn is_down(&self) -> bool {
+        match *self.down_until.lock().unwrap() {
+            Some(until) => Instant::now() < until,
+            None => false,
+        }
+    }
+
+    fn mark_down(&self) {
+        *self.down_until.lock().unwrap() = Some(Instant::now() + BACKEND_COOLDOWN);
+    }
+}
+
+/// Live traffic counters for a single proxy, snapshotted into a [`Metrics`] on demand.
+#[derive(Default)]
+struct MetricsCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    total_connections: AtomicU64,
+    active_connections: AtomicU64,
+}
+
+impl MetricsCounters {
+    fn snapshot(&self) -> Metrics {
+        Metrics {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Decrements a proxy's active-connection counter when the connection it
+/// tracks ends, including when it is aborted rather than returning normally.
+struct ActiveConnGuard<'a>(&'a MetricsCounters);
+
+impl<'a> ActiveConnGuard<'a> {
+    fn new(metrics: &'a MetricsCounters) -> Self {
+        metrics.total_connections.fetch_add(1, Ordering::Relaxed);
+        metrics.active_connections.fetch_add(1, Ordering::Relaxed);
+        Self(metrics)
+    }
+}
+
+impl Drop for ActiveConnGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Copies like [`io::copy`] but adds every chunk read to `counter`.
+async fn counting_copy<R, W>(reader: &mut R, writer: &mut W, counter: &AtomicU64) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buf[..n]).await?;
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Decrements a backend's in-flight counter when the connection it tracks ends,
+/// including when it is aborted rather than returning normally.
+struct InFlightGuard<'a>(&'a Backend);
+
+impl<'a> InFlightGuard<'a> {
+    fn new(backend: &'a Backend) -> Self {
+        backend.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self(backend)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Rebuilds the live backend set from a freshly resolved address list, reusing
+/// `current`'s per-backend state for addresses that persist across the resolve
+/// and only constructing fresh [`Backend`]s for ones that are new. Returns
+/// `None` if `resolved` is exactly the same set of addresses as `current`, so
+/// a re-resolve that didn't actually change anything doesn't reset state for
+/// every backend on a fixed cadence.
+fn rebuild_backends(current: &[Backend], resolved: Vec<ResolvedBackend>) -> Option<Vec<Backend>> {
+    if resolved.len() == current.len()
+        && resolved
+            .iter()
+            .all(|r| current.iter().any(|b| b.addr == r.addr))
+    {
+        return None;
+    }
+    Some(
+        resolved
+            .into_iter()
+            .map(
+                |resolved| match current.iter().find(|b| b.addr == resolved.addr) {
+                    Some(existing) => Backend::carry_over(resolved, existing),
+                    None => Backend::new(resolved),
+                },
+            )
+            .collect(),
+    )
+}
+
+/// Picks the next backend to dial, skipping any currently in their cooldown window.
+fn pick_backend<'a>(
+    backends: &'a [Backend],
+    strategy: LoadBalanceStrategy,
+    round_robin_cursor: &AtomicUsize,
+) -> Option<&'a Backend> {
+    let up: Vec<&Backend> = backends.iter().filter(|b| !b.is_down()).collect();
+    if up.is_empty() {
+        return None;
+    }
+    match strategy {
+        LoadBalanceStrategy::RoundRobin => {
+            let i = round_robin_cursor.fetch_add(1, Ordering::SeqCst) % up.len();
+            Some(up[i])
+        }
+        LoadBalanceStrategy::LeastConnections => up
+            .into_iter()
+            .min_by_key(|b| b.in_flight.load(Ordering::SeqCst)),
+        LoadBalanceStrategy::Random => {
+            // No extra dependency for a single coin flip: `RandomState` draws its
+            // keys from the OS entropy source (cached per-thread, not the clock),
+            // so hashing them gives an actually random index instead of pulling
+            // in `rand`.
+            let i = (RandomState::new().build_hasher().finish() as usize) % up.len();
+            Some(up[i])
+        }
+    }
+}
+
+/// An outbound connection to a backend, kept as its concrete type for as long
+/// as possible so a plain TCP one can be handed back to the connection pool.
+enum Outbound {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+/// Dials the next available backend according to `strategy`, marking backends
+/// down on connection failure and only giving up once all of them failed.
+/// The returned guard's lifetime is the caller's cue for how long to keep the
+/// backend's in-flight counter incremented.
+async fn connect_to_backend<'a>(
+    backends: &'a [Backend],
+    strategy: LoadBalanceStrategy,
+    round_robin_cursor: &AtomicUsize,
+    tls: &ProxyTls,
+    pool: &ConnectionPool,
+    upstream_proxy: Option<&str>,
+) -> Result<(String, Outbound, InFlightGuard<'a>), Box<dyn Error + Send + Sync>> {
+    let mut attempts = 0;
+    loop {
+        let backend = match pick_backend(backends, strategy, round_robin_cursor) {
+            Some(backend) => backend,
+            None => return Err("all backends are down".into()),
+        };
+        let guard = InFlightGuard::new(backend);
+        match dial_backend(&backend.addr, &backend.sni_host, tls, pool, upstream_proxy).await {
+            Ok(stream) => return Ok((backend.addr.clone(), stream, guard)),
+            Err(_) => {
+                drop(guard);
+                backend.mark_down();
+                attempts += 1;
+                if attempts >= backends.len() {
+                    return Err("all backends are down".into());
+                }
+            }
+        }
+    }
+}
+
+/// Dials a single backend, preferring a pre-warmed pooled connection over a
+/// fresh TCP handshake (optionally tunneled through an upstream HTTP CONNECT
+/// proxy), and wrapping the result in TLS (using `sni_host` — the backend's
+/// original hostname, not the address it was dialed at — as SNI) when an
+/// outbound connector is configured.
+async fn dial_backend(
+    addr: &str,
+    sni_host: &str,
+    tls: &ProxyTls,
+    pool: &ConnectionPool,
+    upstream_proxy: Option<&str>,
+) -> Result<Outbound, Box<dyn Error + Send + Sync>> {
+    let stream = match pool.take(addr).await {
+        Some(stream) => stream,
+        None => match upstream_proxy {
+            Some(proxy_addr) => connect_via_upstream_proxy(proxy_addr, addr).await?,
+            None => TcpStream::connect(addr).await?,
+        },
+    };
+    match &tls.outbound_connector {
+        Some(connector) => {
+            let server_name = ServerName::try_from(sni_host)?;
+            Ok(Outbound::Tls(Box::new(
+                connector.connect(server_name, stream).await?,
+            )))
+        }
+        None => Ok(Outbound::Plain(stream)),
+    }
+}
+
+/// Dials `target` through an upstream HTTP CONNECT proxy at `proxy_addr`
+/// (optionally `user:pass@host:port`, the credentials becoming a
+/// `Proxy-Authorization: Basic` header) and returns the raw tunneled stream
+/// once the proxy confirms the tunnel with a `2xx` status.
+async fn connect_via_upstream_proxy(
+    proxy_addr: &str,
+    target: &str,
+) -> Result<TcpStream, Box<dyn Error + Send + Sync>> {
+    let (credentials, proxy_host_port) = match proxy_addr.split_once('@') {
+        Some((credentials, rest)) => (Some(credentials), rest),
+        None => (None, proxy_addr),
+    };
+    let mut stream = TcpStream::connect(proxy_host_port).await?;
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(credentials) = credentials {
+        request.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            BASE64.encode(credentials)
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    let status_line = read_header_line(&mut stream)
+        .await?
+        .ok_or("upstream proxy closed the connection before responding to CONNECT")?;
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or("malformed response status line from upstream proxy")?;
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "upstream proxy refused CONNECT to {}: {}",
+            target,
+            status_line.trim()
+        )
+        .into());
+    }
+    loop {
+        let line = read_header_line(&mut stream).await?.ok_or(
+            "upstream proxy closed the connection before completing the CONNECT handshake",
+        )?;
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+    Ok(stream)
+}
+
+/// Reads a single `\n`-terminated line directly off `stream`, one byte at a
+/// time, instead of through a `BufReader` — which would read ahead past the
+/// blank line ending the CONNECT response and silently drop whatever of the
+/// tunneled payload it buffered along with it. Returns `None` if the stream
+/// closed without yielding any bytes for this line.
+async fn read_header_line(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        if stream.read(&mut byte).await? == 0 {
+            return Ok(if line.is_empty() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&line).into_owned())
+            });
+        }
+        line.push(byte[0]);
+        if byte[0] == b'\n' {
+            return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+        }
+    }
+}
+
+/// Relays bytes between `inbound` and a plain TCP `outbound`, returning the
+/// backend connection to `pool` for reuse if the inbound side finished first
+/// (the backend's own half wasn't shut down, so it's worth keeping warm).
+async fn relay_and_pool(
+    inbound: Box<dyn IoStream>,
+    mut outbound: TcpStream,
+    pool: &ConnectionPool,
+    addr: &str,
+    port: i32,
+    metrics: &MetricsCounters,
+) {
+    let (mut inread, mut inwrite) = io::split(inbound);
+    let remote_closed_or_failed = {
+        let (mut outread, mut outwrite) = outbound.split();
+        // Deliberately does not shut down `outwrite`: doing so would send a FIN to
+        // the backend, making the connection unusable for the next `pool.put` below.
+        let local_to_remote = async { counting_copy(&mut inread, &mut outwrite, &metrics.bytes_in).await };
+        let remote_to_local = async {
+            counting_copy(&mut outread, &mut inwrite, &metrics.bytes_out).await?;
+            inwrite.shutdown().await
+        };
+        tokio::select! {
+            res = local_to_remote => res.is_err(),
+            res = remote_to_local => { if res.is_err() { println!("proxy failed for port {}", port); } true }
+        }
+    };
+    if !remote_closed_or_failed {
+        pool.put(addr, outbound).await;
+    }
+}
+
+/// Relays bytes between `inbound` and `outbound` until both sides are done.
+async fn relay(
+    inbound: Box<dyn IoStream>,
+    outbound: Box<dyn IoStream>,
+    port: i32,
+    metrics: &MetricsCounters,
+) {
+    let (mut inread, mut inwrite) = io::split(inbound);
+    let (mut outread, mut outwrite) = io::split(outbound);
+    let local_to_remote = async {
+        counting_copy(&mut inread, &mut outwrite, &metrics.bytes_in).await?;
+        outwrite.shutdown().await
+    };
+    let remote_to_local = async {
+        counting_copy(&mut outread, &mut inwrite, &metrics.bytes_out).await?;
+        inwrite.shutdown().await
+    };
+    if let Err(_e) = tokio::try_join!(local_to_remote, remote_to_local) {
+        println!("proxy failed for port {}", port);
+    }
+}
+
 pub struct Proxy {
     task: JoinHandle<ProxyResult>,
+    metrics: Arc<MetricsCounters>,
+    stop_accepting: watch::Sender<bool>,
+    drain_timeout: Duration,
 }
 
 impl Proxy {
     pub fn new(
         host: &str,
         port: i32,
-        remote_addr: &str,
+        backends: Vec<String>,
+        strategy: LoadBalanceStrategy,
+        tls: ProxyTls,
+        pool_config: PoolConfig,
+        drain_timeout: Duration,
+        pin_static: bool,
+        upstream_proxy: Option<String>,
+        resolver: Arc<CachingResolver>,
         close_channel: oneshot::Sender<i32>,
     ) -> Self {
-        let remote_addr = remote_addr.to_owned();
         let local_addr = format!("{}:{}", host, port).to_owned();
+        let metrics = Arc::new(MetricsCounters::default());
+        let (stop_tx, mut stop_rx) = watch::channel(false);
         Self {
+            metrics: metrics.clone(),
+            drain_timeout,
             task: tokio::spawn(async move {
+                let backend_specs: Vec<BackendSpec> = backends
+                    .iter()
+                    .filter_map(|addr| BackendSpec::new(addr, pin_static).ok())
+                    .collect();
+                let initial = resolver.expand(&backend_specs).await;
+                let (backends_tx, backends_rx) = watch::channel(Arc::new(
+                    initial.into_iter().map(Backend::new).collect::<Vec<_>>(),
+                ));
+                let resolve_task = {
+                    let resolver = resolver.clone();
+                    let backend_specs = backend_specs.clone();
+                    tokio::spawn(async move {
+                        let mut interval = tokio::time::interval(MIN_REFRESH_INTERVAL);
+                        loop {
+                            interval.tick().await;
+                            let resolved = resolver.expand(&backend_specs).await;
+                            let current = backends_tx.borrow().clone();
+                            if let Some(backends) = rebuild_backends(&current, resolved) {
+                                backends_tx.send(Arc::new(backends)).ok();
+                            }
+                        }
+                    })
+                };
+                let round_robin_cursor = Arc::new(AtomicUsize::new(0));
+                let pool = Arc::new(ConnectionPool::new(pool_config));
+                let evictor = {
+                    let pool = pool.clone();
+                    tokio::spawn(async move {
+                        let mut interval = tokio::time::interval(pool.idle_ttl());
+                        loop {
+                            interval.tick().await;
+                            pool.evict_expired().await;
+                        }
+                    })
+                };
                 let listener = TcpListener::bind(local_addr).await?;
-                while let Ok((mut inbound, _)) = listener.accept().await {
-                    if let Ok(mut outbound) = TcpStream::connect(remote_addr.clone()).await {
-                        let (mut inread, mut inwrite) = inbound.split();
-                        let (mut outread, mut outwrite) = outbound.split();
-                        let local_to_remote = async {
-                            io::copy(&mut inread, &mut outwrite).await?;
-                            outwrite.shutdown().await
-                        };
-                        let remote_to_local = async {
-                            io::copy(&mut outread, &mut inwrite).await?;
-                            inwrite.shutdown().await
+                loop {
+                    let inbound = tokio::select! {
+                        _ = stop_rx.changed() => break,
+                        accept_res = listener.accept() => match accept_res {
+                            Ok((inbound, _)) => inbound,
+                            Err(_) => break,
+                        },
+                    };
+                    // Snapshot the current backend set so a background re-resolve
+                    // mid-connection can't be observed as backends disappearing
+                    // out from under an in-flight request.
+                    let backends = backends_rx.borrow().clone();
+                    let round_robin_cursor = round_robin_cursor.clone();
+                    let tls = tls.clone();
+                    let pool = pool.clone();
+                    let metrics = metrics.clone();
+                    let upstream_proxy = upstream_proxy.clone();
+                    let mut abort_rx = stop_rx.clone();
+                    let mut conn = tokio::spawn(async move {
+                        let _conn_guard = ActiveConnGuard::new(&metrics);
+                        let inbound: Box<dyn IoStream> = match &tls.inbound_acceptor {
+                            Some(acceptor) => match acceptor.accept(inbound).await {
+                                Ok(stream) => Box::new(stream),
+                                Err(_) => return,
+                            },
+                            None => Box::new(inbound),
                         };
-                        if let Err(_e) = tokio::try_join!(local_to_remote, remote_to_local) {
-                            println!("proxy failed for port {}", port);
+                        if let Ok((addr, outbound, _guard)) = connect_to_backend(
+                            &backends,
+                            strategy,
+                            &round_robin_cursor,
+                            &tls,
+                            &pool,
+                            upstream_proxy.as_deref(),
+                        )
+                        .await
+                        {
+                            match outbound {
+                                Outbound::Plain(stream) => {
+                                    relay_and_pool(inbound, stream, &pool, &addr, port, &metrics)
+                                        .await
+                                }
+                                Outbound::Tls(stream) => {
+                                    relay(inbound, stream, port, &metrics).await
+                                }
+                            }
+                        }
+                    });
+                    // The stop sender lives in the `Proxy`, so once the proxy is
+                    // dropped `changed()` errors and a still-running connection is aborted.
+                    tokio::spawn(async move {
+                        tokio::select! {
+                            _ = &mut conn => {}
+                            _ = async { while abort_rx.changed().await.is_ok() {} } => conn.abort(),
                         }
-                    }
+                    });
                 }
+                evictor.abort();
+                resolve_task.abort();
                 close_channel.send(port).map_err(|_| {
                     format!(
                         "failed to signal proxy manager about the end of proxy on port {}",
@@ -49,6 +534,27 @@ impl Proxy {
                 })?;
                 Ok(())
             }),
+            stop_accepting: stop_tx,
+        }
+    }
+
+    /// Returns a snapshot of this proxy's live traffic counters.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    /// Stops accepting new inbound connections and waits for in-flight ones to
+    /// finish on their own, up to this proxy's configured drain timeout.
+    ///
+    /// Connections still active once the timeout elapses are left to be
+    /// aborted when the proxy is dropped.
+    pub async fn drain(&self) {
+        self.stop_accepting.send(true).ok();
+        let deadline = Instant::now() + self.drain_timeout;
+        while self.metrics.active_connections.load(Ordering::Relaxed) > 0
+            && Instant::now() < deadline
+        {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
         }
     }
 }
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use trust_dns_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+
+use crate::types::Error;
+
+/// How a single configured backend address should be turned into the concrete
+/// addresses the load balancer dials.
+#[derive(Debug, Clone)]
+pub enum BackendSpec {
+    /// A pinned address, dialed as-is with DNS resolution bypassed entirely.
+    Static(String),
+    /// A DNS name resolved (and periodically re-resolved) into one backend
+    /// per A/AAAA record, each dialed on `port`.
+    Dns { host: String, port: u16 },
+}
+
+impl BackendSpec {
+    /// Parses a configured `host:port` backend address, resolving it as DNS
+    /// unless `pin_static` asks to bypass resolution entirely.
+    pub fn new(addr: &str, pin_static: bool) -> Result<Self, Error> {
+        if pin_static {
+            return Ok(BackendSpec::Static(addr.to_owned()));
+        }
+        let (host, port) = addr
+            .rsplit_once(':')
+            .ok_or_else(|| format!("backend address {} is missing a port", addr))?;
+        Ok(BackendSpec::Dns {
+            host: host.to_owned(),
+            port: port.parse()?,
+        })
+    }
+}
+
+struct CacheEntry {
+    addrs: Vec<IpAddr>,
+    expires_at: Instant,
+}
+
+/// A backend address expanded from a [`BackendSpec`], ready to be dialed.
+pub struct ResolvedBackend {
+    /// The concrete `ip:port` (or pinned `host:port`) to open the TCP connection to.
+    pub addr: String,
+    /// The hostname to present as TLS SNI and to validate the backend's certificate
+    /// against. For a [`BackendSpec::Dns`] backend this is the original DNS name, not
+    /// the resolved IP `addr` carries — a certificate is issued for the name, not the
+    /// address it happens to resolve to.
+    pub sni_host: String,
+}
+
+/// Resolves backend hostnames through an async DNS resolver, caching answers
+/// for their record TTL and re-resolving once that TTL has elapsed, so a
+/// backend's IP change is picked up without waiting for a fresh connection
+/// to force a lookup.
+pub struct CachingResolver {
+    resolver: TokioAsyncResolver,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl CachingResolver {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())?,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Resolves `host`, serving the cached answer until its TTL expires.
+    async fn resolve_host(&self, host: &str) -> Result<Vec<IpAddr>, Error> {
+        {
+            let cache = self.cache.lock().await;
+            if let Some(entry) = cache.get(host) {
+                if Instant::now() < entry.expires_at {
+                    return Ok(entry.addrs.clone());
+                }
+            }
+        }
+        let lookup = self.resolver.lookup_ip(host).await?;
+        let expires_at = lookup.as_lookup().valid_until();
+        let addrs: Vec<IpAddr> = lookup.iter().collect();
+        if addrs.is_empty() {
+            return Err(format!("no addresses found for {}", host).into());
+        }
+        self.cache.lock().await.insert(
+            host.to_owned(),
+            CacheEntry {
+                addrs: addrs.clone(),
+                expires_at,
+            },
+        );
+        Ok(addrs)
+    }
+
+    /// Expands backend specs into concrete, dialable addresses, resolving
+    /// (and caching) any DNS-based ones so a single name with multiple
+    /// records behaves as multiple load-balanced backends. A spec that fails
+    /// to resolve is dropped rather than failing the whole expansion, the
+    /// same way a backend that fails to connect is just marked down.
+    pub async fn expand(&self, specs: &[BackendSpec]) -> Vec<ResolvedBackend> {
+        let mut out = Vec::new();
+        for spec in specs {
+            match spec {
+                BackendSpec::Static(addr) => {
+                    let sni_host = addr
+                        .rsplit_once(':')
+                        .map(|(host, _)| host.to_owned())
+                        .unwrap_or_else(|| addr.clone());
+                    out.push(ResolvedBackend {
+                        addr: addr.clone(),
+                        sni_host,
+                    });
+                }
+                BackendSpec::Dns { host, port } => match self.resolve_host(host).await {
+                    Ok(addrs) => {
+                        for ip in addrs {
+                            out.push(ResolvedBackend {
+                                addr: format!("{}:{}", ip, port),
+                                sni_host: host.clone(),
+                            });
+                        }
+                    }
+                    Err(_) => continue,
+                },
+            }
+        }
+        out
+    }
+}
+
+/// Default TTL fallback is not needed: record TTLs come straight from the
+/// resolver's answer, but background refreshes are still paced so a name
+/// with a very short TTL doesn't hammer the resolver.
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
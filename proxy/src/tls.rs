@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use rustls_pemfile::{certs, ec_private_keys, pkcs8_private_keys, rsa_private_keys};
+use tokio_rustls::{
+    rustls::{self, Certificate, PrivateKey},
+    TlsAcceptor, TlsConnector,
+};
+
+use crate::types::Error;
+
+/// Builds a [`TlsAcceptor`] for terminating inbound connections from a
+/// PEM-encoded certificate chain and private key.
+pub fn build_tls_acceptor(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsAcceptor, Error> {
+    let cert_chain = certs(&mut &cert_pem[..])?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let key = parse_private_key(key_pem)?;
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Parses a PEM-encoded private key, trying each encoding `rustls_pemfile`
+/// supports in turn (PKCS#8, then PKCS#1/RSA, then SEC1/EC) since a key's
+/// exact form isn't known ahead of time and each parser only recognizes its
+/// own.
+fn parse_private_key(key_pem: &[u8]) -> Result<PrivateKey, Error> {
+    if let Some(key) = pkcs8_private_keys(&mut &key_pem[..])?.pop() {
+        return Ok(PrivateKey(key));
+    }
+    if let Some(key) = rsa_private_keys(&mut &key_pem[..])?.pop() {
+        return Ok(PrivateKey(key));
+    }
+    if let Some(key) = ec_private_keys(&mut &key_pem[..])?.pop() {
+        return Ok(PrivateKey(key));
+    }
+    Err("no private key found in PEM data".into())
+}
+
+/// Builds a [`TlsConnector`] for originating outbound connections, trusting
+/// the platform's native root certificates.
+pub fn build_tls_connector() -> Result<TlsConnector, Error> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        roots.add(&Certificate(cert.0)).ok();
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
@@ -1,17 +1,66 @@
 use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::oneshot;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use crate::pool::PoolConfig;
 
 pub type Error = Box<dyn StdError + Send + Sync>;
 
+/// How long a proxy waits for in-flight connections to finish on their own
+/// after it stops accepting new ones, before they are aborted outright.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a [`crate::proxy::Proxy`] picks a backend out of a service's address set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadBalanceStrategy {
+    RoundRobin,
+    LeastConnections,
+    Random,
+}
+
+/// Whether a proxy leg (inbound from the gate, or outbound to a backend) is
+/// plaintext or TLS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportType {
+    Tcp,
+    Tls,
+}
+
+/// TLS material wired into a proxy: an acceptor to terminate inbound
+/// connections and/or a connector to originate outbound ones.
+#[derive(Clone, Default)]
+pub struct ProxyTls {
+    pub inbound_acceptor: Option<Arc<TlsAcceptor>>,
+    pub outbound_connector: Option<Arc<TlsConnector>>,
+}
+
+/// A point-in-time snapshot of a proxy's traffic counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub total_connections: u64,
+    pub active_connections: u64,
+}
+
 pub enum Request {
     ProxyStatus(i32, oneshot::Sender<Response>),
     CreateProxy {
         host: String,
         port: i32,
-        remote_addr: String,
+        backends: Vec<String>,
+        strategy: LoadBalanceStrategy,
+        tls: ProxyTls,
+        pool_config: PoolConfig,
+        drain_timeout: Duration,
+        pin_static: bool,
+        upstream_proxy: Option<String>,
         response_channel: oneshot::Sender<Response>,
     },
     DeleteProxy(i32, Option<oneshot::Sender<Response>>),
+    ProxyMetrics(i32, oneshot::Sender<Result<Metrics, Error>>),
     Terminate,
 }
 